@@ -11,4 +11,21 @@ impl Config {
             block_period,
         }
     }
+
+    /// Deadline, in milliseconds, a node waits in `round` before giving up and broadcasting a
+    /// round-change vote for `round + 1`.
+    ///
+    /// Backs off exponentially from `request_time` so a validator stuck behind a slow or
+    /// partitioned leader doesn't hammer the network with round changes, but caps the result at
+    /// `16 * block_period` so that even a long-partitioned node eventually retries often enough
+    /// to resynchronize once the partition heals.
+    pub fn round_change_timeout(&self, round: u64) -> u64 {
+        const MAX_BACKOFF_SHIFT: u32 = 8;
+        const MAX_BACKOFF_MULTIPLIER: u64 = 16;
+
+        let shift = round.min(u64::from(MAX_BACKOFF_SHIFT)) as u32;
+        let backoff = self.request_time.saturating_mul(1u64 << shift);
+        let cap = self.block_period.saturating_mul(MAX_BACKOFF_MULTIPLIER);
+        backoff.min(cap)
+    }
 }
\ No newline at end of file
@@ -16,6 +16,7 @@ use crate::{
 };
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 pub trait Commit {
     fn send_commit(&mut self);
@@ -28,6 +29,7 @@ pub trait Commit {
         subject: &Subject,
         sender: Address,
         src: Validator,
+        val_set: &ValidatorSet,
     ) -> Result<(), String>;
     fn accept(&mut self, msg: GossipMessage, src: Validator) -> Result<(), String>;
 }
@@ -66,11 +68,19 @@ impl Commit for Core {
         match msg.address() {
             Ok(sender) => {
                 let subject = Subject::from_bytes(Cow::from(msg.msg()));
-                self.verify_commit(msg.commit_seal.as_ref(), &subject, sender, src.clone())?;
-                self.accept(msg.clone(), src.clone())?;
                 let val_set = self.val_set();
-                // receive more +2/3 votes
-                if self.current_state.commits.len() > val_set.two_thirds_majority()
+                self.verify_commit(
+                    msg.commit_seal.as_ref(),
+                    &subject,
+                    sender,
+                    src.clone(),
+                    &val_set,
+                )?;
+                self.accept(msg.clone(), src.clone())?;
+                // receive more than 2/3 of the total voting power, not just +2/3 of the
+                // validator count, so heavier validators can't be outvoted by a crowd of
+                // zero-power ones and vice versa.
+                if self.commits_voting_power(&val_set) > val_set.two_thirds_majority()
                     && self.state < State::Committed
                 {
                     self.current_state.lock_hash();
@@ -90,6 +100,7 @@ impl Commit for Core {
         commit_subject: &Subject,
         sender: Address,
         src: Validator,
+        val_set: &ValidatorSet,
     ) -> Result<(), String> {
         if commit_seal.is_none() {
             return Err("commit seal is nil".to_string());
@@ -99,6 +110,13 @@ impl Commit for Core {
         verify_address(&sender, commit_seal, &sign_message)
             .map(|_| ())
             .map_err(|_| "message's sender should be commit seal".to_string())?;
+        // A validator with zero voting power cannot move the quorum, so its commit seal
+        // is rejected outright rather than silently counted for nothing.
+        match val_set.get_by_address(&sender) {
+            Some(validator) if validator.voting_power() > 0 => {}
+            Some(_) => return Err("commit sender has zero voting power".to_string()),
+            None => return Err("commit sender is not part of the validator set".to_string()),
+        }
         let current_state = &self.current_state;
         let current_subject = current_state.subject().unwrap();
         if current_subject.digest != commit_subject.digest
@@ -112,4 +130,346 @@ impl Commit for Core {
     fn accept(&mut self, msg: GossipMessage, _: Validator) -> Result<(), String> {
         self.current_state.commits.add(msg.clone())
     }
+}
+
+impl Core {
+    // Proposer selection lives outside this file (on `Core`/`ValidatorSet` themselves, not in
+    // `commit.rs`) and isn't part of this snapshot, but it must apply the same rule as
+    // `verify_commit`/`commits_voting_power` below: a validator with zero voting power is never
+    // eligible to be picked as proposer, exactly as it's excluded from the commit and
+    // round-change quorums.
+    /// Sums the voting power of the validators that have contributed a commit seal for the
+    /// current round. Senders with zero voting power are excluded, mirroring the rejection in
+    /// `verify_commit`, so they can never count towards the quorum.
+    fn commits_voting_power(&self, val_set: &ValidatorSet) -> u64 {
+        self.current_state
+            .commits
+            .iter()
+            .filter_map(|msg| msg.address().ok())
+            .filter_map(|addr| val_set.get_by_address(&addr))
+            .filter(|validator| validator.voting_power() > 0)
+            .fold(0u64, |power, validator| {
+                power.saturating_add(validator.voting_power())
+            })
+    }
+}
+
+/// Round-change votes received for rounds the node hasn't reached yet, keyed by round and then
+/// by sender address.
+///
+/// A node falls behind `RoundChangeSet` by timeout (`Config::round_change_timeout`), not by
+/// missing messages, so the set exists purely to let it catch up faster than waiting out every
+/// intermediate round: `f+1` votes for any higher round prove at least one honest validator has
+/// already moved on, and `+2/3` voting power for a round is enough to start it outright.
+///
+/// Votes are keyed by sender address, so a single validator resending (or duplicating) a
+/// round-change message counts once per round.
+#[derive(Debug, Default)]
+pub struct RoundChangeSet {
+    votes: HashMap<u64, HashMap<Address, GossipMessage>>,
+}
+
+impl RoundChangeSet {
+    pub fn new() -> Self {
+        RoundChangeSet {
+            votes: HashMap::new(),
+        }
+    }
+
+    /// Records `msg` as `sender`'s round-change vote for `round`, replacing any earlier vote from
+    /// the same sender for that round, and returns the number of distinct senders now recorded.
+    pub fn add(&mut self, round: u64, sender: Address, msg: GossipMessage) -> usize {
+        let entry = self.votes.entry(round).or_insert_with(HashMap::new);
+        entry.insert(sender, msg);
+        entry.len()
+    }
+
+    /// Number of distinct senders recorded for `round`.
+    pub fn count(&self, round: u64) -> usize {
+        self.votes.get(&round).map(HashMap::len).unwrap_or(0)
+    }
+
+    /// Sums the voting power of the distinct senders recorded for `round`. Senders that are no
+    /// longer (or never were) part of `val_set`, or hold zero voting power, are excluded —
+    /// mirroring `Core::commits_voting_power`.
+    pub fn voting_power(&self, round: u64, val_set: &ValidatorSet) -> u64 {
+        self.votes
+            .get(&round)
+            .map(|senders| {
+                senders
+                    .keys()
+                    .filter_map(|addr| val_set.get_by_address(addr))
+                    .filter(|validator| validator.voting_power() > 0)
+                    .fold(0u64, |power, validator| {
+                        power.saturating_add(validator.voting_power())
+                    })
+            })
+            .unwrap_or(0)
+    }
+
+    /// Drops every vote for a round lower than `round`: once the node moves to `round` those
+    /// older votes can no longer trigger anything.
+    pub fn clear_below(&mut self, round: u64) {
+        self.votes.retain(|&r, _| r >= round);
+    }
+}
+
+pub trait RoundChange {
+    fn send_round_change(&mut self, round: u64);
+    fn handle_round_change(&mut self, msg: &GossipMessage, src: Validator) -> Result<(), String>;
+}
+
+impl RoundChange for Core {
+    // Broadcasts a round-change vote for `round`. Called once `Config::round_change_timeout`
+    // elapses for the current round without reaching commit.
+    fn send_round_change(&mut self, round: u64) {
+        trace!("broadcast round change to round {}", round);
+        let encoded_round = round.into_bytes();
+        let msg = GossipMessage::new(MessageType::RoundChange, encoded_round, None);
+        self.broadcast(&msg);
+    }
+
+    fn handle_round_change(&mut self, msg: &GossipMessage, _src: Validator) -> Result<(), String> {
+        let round = u64::from_bytes(Cow::from(msg.msg()));
+        let current_round = self
+            .current_state
+            .subject()
+            .map(|subject| subject.view.round)
+            .unwrap_or(0);
+        if round <= current_round {
+            return Err("round change targets a round we've already left behind".to_string());
+        }
+
+        let val_set = self.val_set();
+        let sender = msg
+            .address()
+            .map_err(|_| "round change sender signature is invalid".to_string())?;
+        // A validator with zero voting power cannot move the quorum, so its round-change vote
+        // is rejected outright rather than silently counted for nothing, mirroring
+        // `verify_commit`.
+        match val_set.get_by_address(&sender) {
+            Some(validator) if validator.voting_power() > 0 => {}
+            Some(_) => return Err("round change sender has zero voting power".to_string()),
+            None => return Err("round change sender is not part of the validator set".to_string()),
+        }
+        let received = self.round_changes.add(round, sender, msg.clone());
+        let byzantine_tolerance = (val_set.len() as u64).saturating_sub(1) / 3;
+
+        if (received as u64) >= byzantine_tolerance + 1 && round > current_round {
+            // f+1 distinct validators voted for a higher round: at least one honest validator is
+            // already there, so catch up immediately instead of waiting for our own timeout.
+            self.round_changes.clear_below(round);
+            self.start_round(round);
+            return Ok(());
+        }
+        // +2/3 of the total voting power, not just +2/3 of the validator count, so heavier
+        // validators can't be outvoted by a crowd of zero-power ones and vice versa — the same
+        // weighting `Commit::handle` enforces for commit seals.
+        if self.round_changes.voting_power(round, &val_set) > val_set.two_thirds_majority() {
+            self.round_changes.clear_below(round);
+            self.start_round(round);
+        }
+        Ok(())
+    }
+}
+
+// `cryptocurrency_kit`'s `Hash` is a fixed 32-byte digest.
+const HASH_SIZE: usize = 32;
+
+/// The `+2/3` commit seals collected for a finalized block, assembled once `Commit::handle`
+/// crosses the quorum threshold. Persisted alongside the block so a lagging peer can accept it
+/// straight from `MessageType::SyncResponse` instead of replaying the prepare/commit dance.
+#[derive(Debug, Clone)]
+pub struct CommitCertificate {
+    pub view: View,
+    pub digest: Hash,
+    pub seals: Vec<Signature>,
+}
+
+impl CommitCertificate {
+    pub fn new(view: View, digest: Hash, seals: Vec<Signature>) -> Self {
+        CommitCertificate {
+            view,
+            digest,
+            seals,
+        }
+    }
+
+    /// Verifies every seal against `val_set` and requires their combined voting power to exceed
+    /// `val_set.two_thirds_majority()` — the same threshold `Commit::handle` enforces live, so a
+    /// certificate accepted here could equally have been reached by direct participation.
+    pub fn verify(&self, val_set: &ValidatorSet) -> Result<(), String> {
+        let sign_message = SignMessage::from(self.digest.as_ref());
+        let mut power = 0u64;
+        let mut signers = Vec::with_capacity(self.seals.len());
+        for seal in &self.seals {
+            let signer = recover(seal, &sign_message)
+                .map(|public_key| public_to_address(&public_key))
+                .map_err(|_| "commit certificate carries an unrecoverable seal".to_string())?;
+            if signers.contains(&signer) {
+                return Err("commit certificate carries a duplicate seal".to_string());
+            }
+            let validator = val_set.get_by_address(&signer).ok_or_else(|| {
+                "commit certificate seal signer is not a validator at this height".to_string()
+            })?;
+            if validator.voting_power() == 0 {
+                return Err("commit certificate seal signer has zero voting power".to_string());
+            }
+            power = power.saturating_add(validator.voting_power());
+            signers.push(signer);
+        }
+        if power > val_set.two_thirds_majority() {
+            Ok(())
+        } else {
+            Err("commit certificate does not carry enough voting power for quorum".to_string())
+        }
+    }
+}
+
+impl StorageValue for CommitCertificate {
+    fn into_bytes(self) -> Vec<u8> {
+        let view_bytes = self.view.into_bytes();
+        let mut bytes = (view_bytes.len() as u32).into_bytes();
+        bytes.extend(view_bytes);
+        bytes.extend(self.digest.into_bytes());
+        bytes.extend((self.seals.len() as u32).into_bytes());
+        for seal in self.seals {
+            let seal_bytes = seal.into_bytes();
+            bytes.extend((seal_bytes.len() as u32).into_bytes());
+            bytes.extend(seal_bytes);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self::try_from_bytes(&bytes).expect("malformed CommitCertificate bytes")
+    }
+}
+
+impl CommitCertificate {
+    // A certificate is never expected to carry more seals than there could possibly be
+    // validators; this bounds the `Vec::with_capacity` below so a truncated or forged
+    // `seal_count` can't be used to make a validator allocate an absurd amount of memory before
+    // the length-prefixed reads even get a chance to fail.
+    const MAX_SEALS: usize = 100_000;
+
+    /// Bounds-checked counterpart to `StorageValue::from_bytes`: every fixed- or length-prefixed
+    /// field is read through `take` instead of a raw slice index, so a short or truncated
+    /// certificate (e.g. a forged `SyncResponse` payload) is rejected with `Err` instead of
+    /// panicking the validator.
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut offset = 0usize;
+        let view_len = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+        offset += 4;
+        let view = View::from_bytes(Cow::Borrowed(take(bytes, offset, view_len)?));
+        offset += view_len;
+        let digest = Hash::from_bytes(Cow::Borrowed(take(bytes, offset, HASH_SIZE)?));
+        offset += HASH_SIZE;
+        let seal_count = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+        offset += 4;
+        if seal_count > Self::MAX_SEALS {
+            return Err("commit certificate claims an implausible number of seals".to_string());
+        }
+        let mut seals = Vec::with_capacity(seal_count);
+        for _ in 0..seal_count {
+            let seal_len = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+            offset += 4;
+            seals.push(Signature::from_bytes(Cow::Borrowed(take(
+                bytes, offset, seal_len,
+            )?)));
+            offset += seal_len;
+        }
+        Ok(CommitCertificate {
+            view,
+            digest,
+            seals,
+        })
+    }
+}
+
+/// A block paired with the `CommitCertificate` that finalized it, as streamed back by
+/// `MessageType::SyncResponse` for a contiguous height range.
+#[derive(Debug, Clone)]
+pub struct CertifiedBlock {
+    pub height: u64,
+    pub block_bytes: Vec<u8>,
+    pub certificate: CommitCertificate,
+}
+
+// `bytes` is attacker-controlled gossip payload: every fixed- or length-prefixed field read out
+// of a sync message goes through this instead of a raw `&bytes[..]` index, so a short or
+// truncated message is rejected with `Err` instead of panicking the validator.
+fn take(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], String> {
+    bytes
+        .get(offset..offset + len)
+        .ok_or_else(|| "sync message is truncated".to_string())
+}
+
+pub trait Sync {
+    fn request_sync(&mut self, from_height: u64, to_height: u64);
+    fn handle_sync_request(&mut self, msg: &GossipMessage, src: Validator) -> Result<(), String>;
+    fn handle_sync_response(&mut self, msg: &GossipMessage, src: Validator) -> Result<(), String>;
+}
+
+impl Sync for Core {
+    // Asks peers for every certified block in `[from_height, to_height]`. Modeled on bundle-sync
+    // style catch-up: request the missing range, append what comes back, advance, and repeat
+    // until the node is caught up to its peers' reported height.
+    fn request_sync(&mut self, from_height: u64, to_height: u64) {
+        trace!("request sync from {} to {}", from_height, to_height);
+        let mut payload = from_height.into_bytes();
+        payload.extend(to_height.into_bytes());
+        let msg = GossipMessage::new(MessageType::SyncRequest, payload, None);
+        self.broadcast(&msg);
+    }
+
+    fn handle_sync_request(&mut self, msg: &GossipMessage, src: Validator) -> Result<(), String> {
+        let bytes = msg.msg();
+        let from_height = u64::from_bytes(Cow::Borrowed(take(bytes, 0, 8)?));
+        let to_height = u64::from_bytes(Cow::Borrowed(take(bytes, 8, 8)?));
+        if from_height > to_height {
+            return Err("sync request has an empty or inverted height range".to_string());
+        }
+
+        let mut payload = Vec::new();
+        let blocks = self.certified_blocks(from_height, to_height);
+        payload.extend((blocks.len() as u32).into_bytes());
+        for block in blocks {
+            let block_bytes = block.block_bytes;
+            let cert_bytes = block.certificate.into_bytes();
+            payload.extend(block.height.into_bytes());
+            payload.extend((block_bytes.len() as u32).into_bytes());
+            payload.extend(block_bytes);
+            payload.extend((cert_bytes.len() as u32).into_bytes());
+            payload.extend(cert_bytes);
+        }
+        let response = GossipMessage::new(MessageType::SyncResponse, payload, None);
+        self.send_to(&response, src);
+        Ok(())
+    }
+
+    fn handle_sync_response(&mut self, msg: &GossipMessage, _src: Validator) -> Result<(), String> {
+        let bytes = msg.msg();
+        let val_set = self.val_set();
+        let mut offset = 0usize;
+        let count = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+        offset += 4;
+        for _ in 0..count {
+            let height = u64::from_bytes(Cow::Borrowed(take(bytes, offset, 8)?));
+            offset += 8;
+            let block_len = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+            offset += 4;
+            let block_bytes = take(bytes, offset, block_len)?.to_vec();
+            offset += block_len;
+            let cert_len = u32::from_bytes(Cow::Borrowed(take(bytes, offset, 4)?)) as usize;
+            offset += 4;
+            let certificate = CommitCertificate::try_from_bytes(take(bytes, offset, cert_len)?)?;
+            offset += cert_len;
+
+            // A valid certificate lets the block finalize without replaying prepare/commit.
+            certificate.verify(&val_set)?;
+            self.import_certified_block(height, block_bytes, certificate);
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
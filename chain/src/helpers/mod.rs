@@ -26,12 +26,21 @@ use env_logger::{Builder, Formatter};
 use colored::*;
 use chrono::{DateTime, Local};
 
+use std::collections::hash_map::DefaultHasher;
 use std::env;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::SystemTime;
 
 use crypto::gen_keypair;
 
+/// Set to anything but `0`/empty to redact hex-encoded addresses and hashes out of log lines.
+const REDACT_ENV_VAR: &str = "CONSENSUE_REDACT";
+// `Address` is 20 bytes, `Hash` is 32 bytes; both are logged hex-encoded.
+const ADDRESS_HEX_LEN: usize = 40;
+const HASH_HEX_LEN: usize = 64;
+
 mod types;
 
 /// Performs the logger initialization.
@@ -80,7 +89,7 @@ fn format_log_record(buf: &mut Formatter, record: &Record) -> io::Result<()> {
         module.to_string()
     };
 
-    if has_colors() {
+    let line = if has_colors() {
         let level = match record.level() {
             Level::Error => "ERROR".red(),
             Level::Warn => "WARN".yellow(),
@@ -88,8 +97,7 @@ fn format_log_record(buf: &mut Formatter, record: &Record) -> io::Result<()> {
             Level::Debug => "DEBUG".cyan(),
             Level::Trace => "TRACE".white(),
         };
-        writeln!(
-            buf,
+        format!(
             "{} {} {} {}",
             time.dimmed(),
             level,
@@ -104,6 +112,96 @@ fn format_log_record(buf: &mut Formatter, record: &Record) -> io::Result<()> {
             Level::Debug => "DEBUG",
             Level::Trace => "TRACE",
         };
-        writeln!(buf, "{} {} {} {}", time, level, &source_path, record.args())
+        format!("{} {} {} {}", time, level, &source_path, record.args())
+    };
+
+    if redaction_enabled() {
+        writeln!(buf, "{}", redact_line(&line))
+    } else {
+        writeln!(buf, "{}", line)
+    }
+}
+
+fn redaction_enabled() -> bool {
+    env::var(REDACT_ENV_VAR)
+        .map(|val| val != "0" && !val.is_empty())
+        .unwrap_or(false)
+}
+
+/// Mixed into every redaction token so tokens can't be reversed or compared across
+/// independently-run processes, while staying fixed for this process's lifetime so the same
+/// entity maps to the same token across every line it appears in.
+fn redaction_key() -> u64 {
+    static KEY: AtomicU64 = AtomicU64::new(0);
+
+    let cached = KEY.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+
+    let (_, secret_key) = gen_keypair();
+    let mut hasher = DefaultHasher::new();
+    secret_key.as_ref().hash(&mut hasher);
+    let key = match hasher.finish() {
+        0 => 1,
+        key => key,
+    };
+    // Two threads can both race past the `cached == 0` check on first use; only one of their
+    // generated keys may actually win `KEY`, so always return whichever value ended up stored
+    // rather than the key this call happened to generate, or the same entity could redact to two
+    // different tokens across early log lines.
+    match KEY.compare_exchange(0, key, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => key,
+        Err(winner) => winner,
+    }
+}
+
+fn redact_token(hex_value: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    redaction_key().hash(&mut hasher);
+    hex_value.hash(&mut hasher);
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+fn is_hex_digit(byte: u8) -> bool {
+    byte.is_ascii_hexdigit()
+}
+
+/// Replaces hex-encoded 20-byte addresses and 32-byte hashes found anywhere in `line` with short
+/// stable tokens (e.g. `addr:9f3a1c2b`, `hash:2cce1a04`) derived from a keyed hash of the value,
+/// so the same entity stays correlatable across log lines while the real value is never printed.
+fn redact_line(line: &str) -> String {
+    let bytes = line.as_bytes();
+    let mut output = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if is_hex_digit(bytes[i]) {
+            let mut end = i;
+            while end < bytes.len() && is_hex_digit(bytes[end]) {
+                end += 1;
+            }
+            let run_len = end - i;
+            let label = if run_len == ADDRESS_HEX_LEN {
+                Some("addr")
+            } else if run_len == HASH_HEX_LEN {
+                Some("hash")
+            } else {
+                None
+            };
+            if let Some(label) = label {
+                output.push_str(label);
+                output.push(':');
+                output.push_str(&redact_token(&line[i..end]));
+                i = end;
+                continue;
+            }
+            output.push_str(&line[i..end]);
+            i = end;
+            continue;
+        }
+        let ch = line[i..].chars().next().expect("valid utf8 boundary");
+        output.push(ch);
+        i += ch.len_utf8();
     }
+    output
 }
\ No newline at end of file
@@ -24,8 +24,8 @@ use std::{fmt, u8};
 use std::convert::Into;
 
 use messages::{Message, RawTransaction};
-use storage::{Fork, StorageValue};
-use crypto::{CryptoHash, Hash};
+use storage::{Change, Fork, MapIndex, Snapshot, StorageValue};
+use crypto::{self, CryptoHash, Hash, PublicKey, SecretKey, Signature};
 use encoding;
 use encoding::serialize::json::ExonumJson;
 
@@ -36,6 +36,15 @@ const MAX_ERROR_CODE: u16 = u8::MAX as u16;
 const TRANSACTION_STATUS_OK: u16 = MAX_ERROR_CODE + 1;
 // `Err(TransactionErrorType::Panic)`.
 const TRANSACTION_STATUS_PANIC: u16 = TRANSACTION_STATUS_OK + 1;
+// `Err(TransactionErrorType::Structured { .. })`. Readers older than this sentinel only ever
+// wrote `TRANSACTION_STATUS_OK`/`_PANIC`/a `0..=MAX_ERROR_CODE` code, so this value can never
+// collide with bytes they produced; the tagged payload that follows is only ever read once this
+// exact sentinel is seen, which keeps old encodings decoding exactly as before.
+const TRANSACTION_STATUS_STRUCTURED: u16 = TRANSACTION_STATUS_PANIC + 1;
+// `Err(TransactionErrorType::OutOfResources)`: the framework aborted execution because
+// `ExecutionContext::charge` exceeded its gas budget. Carries no extra payload, so it hashes and
+// encodes exactly like `_PANIC` — just a distinct sentinel.
+const TRANSACTION_STATUS_OUT_OF_RESOURCES: u16 = TRANSACTION_STATUS_STRUCTURED + 1;
 
 /// Return value of the `Transaction`'s `execute' method. Changes made by the transaction are
 /// discarded if `Err` is returned, see `Transaction` documentation for the details.
@@ -71,7 +80,7 @@ pub trait Transaction: ExonumJson + 'static {
     /// use exonum::blockchain::Transaction;
     /// use exonum::crypto::PublicKey;
     /// use exonum::messages::Message;
-    /// # use exonum::blockchain::ExecutionResult;
+    /// # use exonum::blockchain::{ExecutionContext, ExecutionResult};
     /// # use exonum::storage::Fork;
     ///
     /// transactions! {
@@ -92,7 +101,7 @@ pub trait Transaction: ExonumJson + 'static {
     ///
     ///     // Other methods...
     ///     // ...
-    /// #   fn execute(&self, _: &mut Fork) -> ExecutionResult { Ok(()) }
+    /// #   fn execute(&self, _: &mut ExecutionContext) -> ExecutionResult { Ok(()) }
     /// }
     /// # fn main() {}
     fn verify(&self) -> bool;
@@ -114,9 +123,8 @@ pub trait Transaction: ExonumJson + 'static {
     /// ```
     /// # #[macro_use] extern crate exonum;
     /// #
-    /// use exonum::blockchain::{Transaction, ExecutionResult};
+    /// use exonum::blockchain::{ExecutionContext, ExecutionResult, Transaction};
     /// use exonum::crypto::PublicKey;
-    /// use exonum::storage::Fork;
     ///
     /// transactions! {
     ///     MyTransactions {
@@ -130,10 +138,13 @@ pub trait Transaction: ExonumJson + 'static {
     /// }
     ///
     /// impl Transaction for MyTransaction {
-    ///     fn execute(&self, fork: &mut Fork) -> ExecutionResult {
+    ///     fn execute(&self, context: &mut ExecutionContext) -> ExecutionResult {
     ///         // Read and/or write into storage.
     ///         // ...
     ///
+    ///         // Emit an event other services/clients can later look up by this tx's hash.
+    ///         context.emit_event(0, b"hello".to_vec());
+    ///
     ///         // Return execution status.
     ///         Ok(())
     ///     }
@@ -143,7 +154,250 @@ pub trait Transaction: ExonumJson + 'static {
     /// #   fn verify(&self) -> bool { true }
     /// }
     /// # fn main() {}
-    fn execute(&self, fork: &mut Fork) -> ExecutionResult;
+    fn execute(&self, context: &mut ExecutionContext) -> ExecutionResult;
+}
+
+/// Execution context passed into [`Transaction::execute`](trait.Transaction.html#tymethod.execute),
+/// bundling the writable [`Fork`] with side channels services use to emit [`ContractEvent`]s and,
+/// optionally, meter their own resource usage — without widening `Fork` itself.
+pub struct ExecutionContext<'a> {
+    fork: &'a mut Fork,
+    events: Vec<ContractEvent>,
+    gas_budget: Option<u64>,
+    gas_used: u64,
+}
+
+impl<'a> ExecutionContext<'a> {
+    /// Creates a new, unmetered context wrapping `fork` with an empty event log. `charge` never
+    /// fails on a context built this way.
+    pub fn new(fork: &'a mut Fork) -> Self {
+        ExecutionContext {
+            fork,
+            events: Vec::new(),
+            gas_budget: None,
+            gas_used: 0,
+        }
+    }
+
+    /// Creates a context that aborts once `charge` calls accumulate past `gas_budget`, so a
+    /// misbehaving or expensive `execute` can be cut off deterministically.
+    pub fn with_gas_budget(fork: &'a mut Fork, gas_budget: u64) -> Self {
+        ExecutionContext {
+            fork,
+            events: Vec::new(),
+            gas_budget: Some(gas_budget),
+            gas_used: 0,
+        }
+    }
+
+    /// Returns the wrapped fork for services to read and/or write blockchain state.
+    pub fn fork(&mut self) -> &mut Fork {
+        self.fork
+    }
+
+    /// Records an event produced while executing the transaction. Events are persisted keyed by
+    /// the transaction's hash once it commits, in the order they were emitted.
+    pub fn emit_event(&mut self, event_type: u16, payload: Vec<u8>) {
+        self.events.push(ContractEvent::new(event_type, payload));
+    }
+
+    /// Consumes the context, returning the events accumulated during execution. Called by the
+    /// blockchain once `execute` returns, to persist them via `Schema::transaction_events`.
+    pub fn into_events(self) -> Vec<ContractEvent> {
+        self.events
+    }
+
+    /// Charges `units` of gas against the budget passed to `with_gas_budget`, returning `Err(())`
+    /// once the cumulative charge exceeds it. A context built with `new` has no budget and never
+    /// refuses a charge. Callers are expected to bail out of `execute` (returning an `Err`) as
+    /// soon as a charge fails; `finalize_execution_result` enforces the budget regardless, in
+    /// case a transaction ignores that and returns `Ok` anyway.
+    pub fn charge(&mut self, units: u64) -> Result<(), ()> {
+        self.gas_used = self.gas_used.saturating_add(units);
+        match self.gas_budget {
+            Some(budget) if self.gas_used > budget => Err(()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gas charged so far. Persisted per transaction hash (mirroring `transaction_results()` via
+    /// a `Schema::transaction_gas(hash)` accessor) so it contributes deterministically to the
+    /// state hash across validators.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+}
+
+/// Turns the `ExecutionResult` returned by `Transaction::execute` into the `TransactionResult`
+/// actually recorded for `context`'s transaction: if the gas budget was exceeded, the result is
+/// forced to `TransactionErrorType::OutOfResources` regardless of what `execute` returned, since
+/// any partial work it did must be discarded exactly like an `Err`.
+pub fn finalize_execution_result(
+    context: &ExecutionContext,
+    result: ExecutionResult,
+) -> TransactionResult {
+    match context.gas_budget {
+        Some(budget) if context.gas_used > budget => Err(TransactionError::out_of_resources(Some(
+            format!("charged {} against a budget of {}", context.gas_used, budget),
+        ))),
+        _ => result.map_err(TransactionError::from),
+    }
+}
+
+/// A typed event emitted by a service while processing a transaction, analogous to Diem/Libra's
+/// `ContractEvent`. Events are keyed by the emitting transaction's hash and persisted alongside
+/// `transaction_results()`, retrievable through `Schema::transaction_events(hash)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractEvent {
+    event_type: u16,
+    payload: Vec<u8>,
+}
+
+impl ContractEvent {
+    /// Creates a new event of `event_type` carrying the service-defined `payload`.
+    pub fn new(event_type: u16, payload: Vec<u8>) -> Self {
+        ContractEvent {
+            event_type,
+            payload,
+        }
+    }
+
+    /// Service-defined discriminant for this event's shape.
+    pub fn event_type(&self) -> u16 {
+        self.event_type
+    }
+
+    /// Raw, service-defined event contents.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl StorageValue for ContractEvent {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = u16::into_bytes(self.event_type);
+        bytes.extend(self.payload);
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let event_type = u16::from_bytes(Cow::Borrowed(&bytes[0..2]));
+        let payload = bytes[2..].to_vec();
+        ContractEvent {
+            event_type,
+            payload,
+        }
+    }
+}
+
+impl CryptoHash for ContractEvent {
+    fn hash(&self) -> Hash {
+        let mut bytes = u16::into_bytes(self.event_type);
+        bytes.extend_from_slice(&self.payload);
+        crypto::hash(&bytes)
+    }
+}
+
+/// The ordered list of events a single transaction emitted, as returned by
+/// `Schema::transaction_events(hash)`. Folding this list's hash per block into the state hash
+/// (alongside `transaction_results()`) lets clients authenticate "transaction X emitted exactly
+/// these events" the same way they already authenticate its execution status.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionEvents(Vec<ContractEvent>);
+
+impl TransactionEvents {
+    pub fn new(events: Vec<ContractEvent>) -> Self {
+        TransactionEvents(events)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ContractEvent> {
+        self.0.iter()
+    }
+}
+
+impl StorageValue for TransactionEvents {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = (self.0.len() as u32).into_bytes();
+        for event in self.0 {
+            let event_bytes = event.into_bytes();
+            bytes.extend((event_bytes.len() as u32).into_bytes());
+            bytes.extend(event_bytes);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0usize;
+        let count = u32::from_bytes(Cow::Borrowed(&bytes[offset..offset + 4])) as usize;
+        offset += 4;
+        let mut events = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = u32::from_bytes(Cow::Borrowed(&bytes[offset..offset + 4])) as usize;
+            offset += 4;
+            events.push(ContractEvent::from_bytes(Cow::Borrowed(
+                &bytes[offset..offset + len],
+            )));
+            offset += len;
+        }
+        TransactionEvents(events)
+    }
+}
+
+impl CryptoHash for TransactionEvents {
+    fn hash(&self) -> Hash {
+        let mut bytes = (self.0.len() as u32).into_bytes();
+        for event in &self.0 {
+            bytes.extend(event.hash().into_bytes());
+        }
+        crypto::hash(&bytes)
+    }
+}
+
+// Index names for the per-transaction maps this series adds. `transaction_results` (already
+// assumed to exist on `Schema` elsewhere in the blockchain module) follows the same convention.
+const TRANSACTION_EVENTS: &str = "transaction_events";
+const TRANSACTION_GAS: &str = "transaction_gas";
+
+/// The accessors this series adds to `Schema`: the events a transaction emitted and the gas it
+/// was charged, both looked up by the transaction's hash exactly like `transaction_results()`
+/// already is. The rest of `Schema` (`transactions()`, `transaction_results()`, block/height
+/// indexes, ...) lives with the blockchain commit pipeline, outside this module.
+pub struct Schema<T> {
+    view: T,
+}
+
+impl<T: AsRef<Snapshot>> Schema<T> {
+    pub fn new(view: T) -> Self {
+        Schema { view }
+    }
+
+    /// Events emitted while executing the transaction with hash `tx_hash`, in emission order.
+    /// `None` if the transaction was never executed against this view (or emitted nothing and
+    /// was never recorded).
+    pub fn transaction_events(&self, tx_hash: &Hash) -> Option<TransactionEvents> {
+        MapIndex::new(TRANSACTION_EVENTS, self.view.as_ref()).get(tx_hash)
+    }
+
+    /// Gas charged while executing the transaction with hash `tx_hash`. `None` if the transaction
+    /// was never executed against this view.
+    pub fn transaction_gas(&self, tx_hash: &Hash) -> Option<u64> {
+        MapIndex::new(TRANSACTION_GAS, self.view.as_ref()).get(tx_hash)
+    }
+}
+
+impl<'a> Schema<&'a mut Fork> {
+    /// Records `events` for `tx_hash`. Called once by whatever executes the transaction
+    /// (`simulate_transaction`, `PrivateTransaction::execute`, or the real commit pipeline),
+    /// immediately after `ExecutionContext::into_events` is taken.
+    pub(crate) fn set_transaction_events(&mut self, tx_hash: Hash, events: TransactionEvents) {
+        MapIndex::new(TRANSACTION_EVENTS, &mut *self.view).put(&tx_hash, events)
+    }
+
+    /// Records `gas_used` for `tx_hash`. Called once by whatever executes the transaction,
+    /// immediately after `ExecutionContext::gas_used` is read.
+    pub(crate) fn set_transaction_gas(&mut self, tx_hash: Hash, gas_used: u64) {
+        MapIndex::new(TRANSACTION_GAS, &mut *self.view).put(&tx_hash, gas_used)
+    }
 }
 
 /// Result of unsuccessful transaction execution.
@@ -174,6 +428,55 @@ impl ExecutionError {
     }
 }
 
+/// Broad category of a `Structured` transaction error, mirroring the split Diem's `VMStatus`
+/// draws between `StatusType`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCategory {
+    /// Failed static verification; could have been caught before the transaction was included in
+    /// a block.
+    Verification,
+    /// An invariant of the execution engine itself was violated, rather than a service-defined
+    /// check failing.
+    InvariantViolation,
+    /// A regular, service-defined execution failure.
+    Execution,
+    /// Execution was aborted because it exceeded a resource budget (e.g. gas).
+    OutOfResources,
+}
+
+impl ErrorCategory {
+    fn as_u8(self) -> u8 {
+        match self {
+            ErrorCategory::Verification => 0,
+            ErrorCategory::InvariantViolation => 1,
+            ErrorCategory::Execution => 2,
+            ErrorCategory::OutOfResources => 3,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ErrorCategory::Verification,
+            1 => ErrorCategory::InvariantViolation,
+            2 => ErrorCategory::Execution,
+            3 => ErrorCategory::OutOfResources,
+            _ => panic!("Invalid ErrorCategory value: {}", value),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ErrorCategory::Verification => "Verification",
+            ErrorCategory::InvariantViolation => "InvariantViolation",
+            ErrorCategory::Execution => "Execution",
+            ErrorCategory::OutOfResources => "OutOfResources",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Type of the transaction error.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TransactionErrorType {
@@ -182,6 +485,17 @@ pub enum TransactionErrorType {
     /// User-defined error code. Can have different meanings for different transactions and
     /// services.
     Code(u8),
+    /// A richer error that separates the failure `category` from the specific `code` and,
+    /// optionally, pinpoints where execution aborted via `location` (`(service_id, sub_code)`).
+    /// Borrowed from Diem's `VMStatus`/`StatusCode`/`StatusType` split.
+    Structured {
+        category: ErrorCategory,
+        code: u16,
+        location: Option<(u16, u16)>,
+    },
+    /// Execution was aborted by the framework because it exceeded its gas budget
+    /// (`ExecutionContext::charge`), distinct from both a panic and a service-defined `Code`.
+    OutOfResources,
 }
 
 /// Result of unsuccessful transaction execution encompassing both service and framework-wide error
@@ -245,6 +559,30 @@ impl TransactionError {
         Self::new(TransactionErrorType::Panic, description)
     }
 
+    /// Creates a new `TransactionError` representing an aborted-on-gas execution with the given
+    /// description.
+    pub(crate) fn out_of_resources(description: Option<String>) -> Self {
+        Self::new(TransactionErrorType::OutOfResources, description)
+    }
+
+    /// Creates a new `TransactionError` instance with the specified category, code, optional
+    /// abort location and description.
+    pub(crate) fn structured(
+        category: ErrorCategory,
+        code: u16,
+        location: Option<(u16, u16)>,
+        description: Option<String>,
+    ) -> Self {
+        Self::new(
+            TransactionErrorType::Structured {
+                category,
+                code,
+                location,
+            },
+            description,
+        )
+    }
+
     /// Creates a new `TransactionError` instance from `std::thread::Result`'s `Err`.
     pub(crate) fn from_panic(panic: &Box<Any + Send>) -> Self {
         Self::panic(panic_description(panic))
@@ -272,6 +610,19 @@ impl fmt::Display for TransactionError {
         match self.error_type {
             TransactionErrorType::Panic => write!(f, "Panic during execution")?,
             TransactionErrorType::Code(c) => write!(f, "Error code: {}", c)?,
+            TransactionErrorType::Structured {
+                category,
+                code,
+                location,
+            } => {
+                write!(f, "{} error, code: {}", category, code)?;
+                if let Some((service_id, sub_code)) = location {
+                    write!(f, ", aborted at service {} sub-code {}", service_id, sub_code)?;
+                }
+            }
+            TransactionErrorType::OutOfResources => {
+                write!(f, "Execution aborted: out of resources")?
+            }
         }
 
         if let Some(ref description) = self.description {
@@ -285,8 +636,32 @@ impl fmt::Display for TransactionError {
 // String content (`TransactionError::Description`) is intentionally excluded from the hash
 // calculation because user can be tempted to use error description from a third-party libraries
 // which aren't stable across the versions.
+//
+// `Structured` errors hash more than `status_as_u16` carries (category and abort location, not
+// just a code), so they're hashed separately; every other variant keeps hashing the plain `u16`
+// exactly as before so existing state hashes don't change.
 impl CryptoHash for TransactionResult {
     fn hash(&self) -> Hash {
+        if let Err(ref error) = *self {
+            if let TransactionErrorType::Structured {
+                category,
+                code,
+                location,
+            } = error.error_type
+            {
+                let mut bytes = vec![category.as_u8()];
+                bytes.extend(u16::into_bytes(code));
+                match location {
+                    Some((service_id, sub_code)) => {
+                        bytes.push(1);
+                        bytes.extend(u16::into_bytes(service_id));
+                        bytes.extend(u16::into_bytes(sub_code));
+                    }
+                    None => bytes.push(0),
+                }
+                return crypto::hash(&bytes);
+            }
+        }
         u16::hash(&status_as_u16(self))
     }
 }
@@ -302,10 +677,33 @@ impl From<ExecutionError> for TransactionError {
 
 // `TransactionResult` is stored as `u16` plus `bool` (`true` means that optional part is present)
 // with optional string part needed only for string error description.
+//
+// `Structured` widens this into a tagged format: `TRANSACTION_STATUS_STRUCTURED` as the `u16`
+// main part is followed by a category byte, a `u16` code and an optional `(service_id, sub_code)`
+// location, *before* the existing description tail. Every other `u16` value is decoded exactly
+// as it always was, so old bytes keep reading back correctly.
 impl StorageValue for TransactionResult {
     fn into_bytes(self) -> Vec<u8> {
         let mut res = u16::into_bytes(status_as_u16(&self));
-        if let Some(description) = self.err().and_then(|e| e.description) {
+        let err = self.err();
+        if let Some(TransactionErrorType::Structured {
+            category,
+            code,
+            location,
+        }) = err.as_ref().map(|e| e.error_type)
+        {
+            res.push(category.as_u8());
+            res.extend(u16::into_bytes(code));
+            match location {
+                Some((service_id, sub_code)) => {
+                    res.push(1);
+                    res.extend(u16::into_bytes(service_id));
+                    res.extend(u16::into_bytes(sub_code));
+                }
+                None => res.push(0),
+            }
+        }
+        if let Some(description) = err.and_then(|e| e.description) {
             res.extend(bool::into_bytes(true));
             res.extend(String::into_bytes(description));
         } else {
@@ -315,28 +713,65 @@ impl StorageValue for TransactionResult {
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        let main_part = u16::from_bytes(Cow::Borrowed(&bytes));
-        let description = if bool::from_bytes(Cow::Borrowed(&bytes[2..3])) {
-            Some(String::from_bytes(Cow::Borrowed(&bytes[3..])))
-        } else {
-            None
-        };
+        let main_part = u16::from_bytes(Cow::Borrowed(&bytes[0..2]));
 
         match main_part {
-            value @ 0...MAX_ERROR_CODE => Err(TransactionError::code(value as u8, description)),
+            value @ 0...MAX_ERROR_CODE => {
+                let description = description_tail(&bytes, 2);
+                Err(TransactionError::code(value as u8, description))
+            }
             TRANSACTION_STATUS_OK => Ok(()),
-            TRANSACTION_STATUS_PANIC => Err(TransactionError::panic(description)),
+            TRANSACTION_STATUS_PANIC => {
+                let description = description_tail(&bytes, 2);
+                Err(TransactionError::panic(description))
+            }
+            TRANSACTION_STATUS_OUT_OF_RESOURCES => {
+                let description = description_tail(&bytes, 2);
+                Err(TransactionError::out_of_resources(description))
+            }
+            TRANSACTION_STATUS_STRUCTURED => {
+                let category = ErrorCategory::from_u8(bytes[2]);
+                let code = u16::from_bytes(Cow::Borrowed(&bytes[3..5]));
+                let mut offset = 6;
+                let location = if bytes[5] != 0 {
+                    let service_id = u16::from_bytes(Cow::Borrowed(&bytes[offset..offset + 2]));
+                    let sub_code = u16::from_bytes(Cow::Borrowed(&bytes[offset + 2..offset + 4]));
+                    offset += 4;
+                    Some((service_id, sub_code))
+                } else {
+                    None
+                };
+                let description = description_tail(&bytes, offset);
+                Err(TransactionError::structured(
+                    category,
+                    code,
+                    location,
+                    description,
+                ))
+            }
             value => panic!("Invalid TransactionResult value: {}", value),
         }
     }
 }
 
+// Shared tail of the encoding: a presence `bool` at `offset`, followed by the description string
+// when present.
+fn description_tail(bytes: &[u8], offset: usize) -> Option<String> {
+    if bool::from_bytes(Cow::Borrowed(&bytes[offset..offset + 1])) {
+        Some(String::from_bytes(Cow::Borrowed(&bytes[offset + 1..])))
+    } else {
+        None
+    }
+}
+
 fn status_as_u16(status: &TransactionResult) -> u16 {
     match *status {
         Ok(()) => TRANSACTION_STATUS_OK,
         Err(ref e) => match e.error_type {
             TransactionErrorType::Panic => TRANSACTION_STATUS_PANIC,
             TransactionErrorType::Code(c) => u16::from(c),
+            TransactionErrorType::Structured { .. } => TRANSACTION_STATUS_STRUCTURED,
+            TransactionErrorType::OutOfResources => TRANSACTION_STATUS_OUT_OF_RESOURCES,
         },
     }
 }
@@ -350,6 +785,426 @@ pub trait TransactionSet
     fn tx_from_raw(raw: RawTransaction) -> Result<Self, encoding::Error>;
 }
 
+/// An encrypted transaction, modeled after OpenEthereum's private transactions: the inner
+/// `RawTransaction` is encrypted and gossiped as-is, executed only by a holder of the decryption
+/// key against a fork that is never merged into public state, and the public chain only ever
+/// learns a [`PrivateTransactionReceipt`](struct.PrivateTransactionReceipt.html) in its place.
+#[derive(Debug, Clone)]
+pub struct PrivateTransaction {
+    encrypted_payload: Vec<u8>,
+    permitted_validators: Vec<PublicKey>,
+    sender: PublicKey,
+    signature: Signature,
+}
+
+impl PrivateTransaction {
+    pub fn new(
+        encrypted_payload: Vec<u8>,
+        permitted_validators: Vec<PublicKey>,
+        sender: PublicKey,
+        signature: Signature,
+    ) -> Self {
+        PrivateTransaction {
+            encrypted_payload,
+            permitted_validators,
+            sender,
+            signature,
+        }
+    }
+
+    /// Validates the outer envelope *without* decrypting the payload: the signature must match
+    /// `sender`, and `sender` must be one of `permitted_validators`. Whether the payload itself
+    /// is a valid transaction can only be determined by a permitted validator that decrypts it.
+    pub fn verify(&self) -> bool {
+        self.permitted_validators.contains(&self.sender)
+            && crypto::verify(&self.signature, &self.encrypted_payload, &self.sender)
+    }
+
+    /// Decrypts the payload with `key` and reconstructs the inner transaction via
+    /// `TransactionSet::tx_from_raw`, executes it against `fork` (optionally metered by
+    /// `gas_budget`, exactly like `simulate_transaction`), and returns only the public receipt.
+    /// `fork` is expected to be a private, throwaway fork consumed here: the caller must never
+    /// merge it into the public blockchain state, since doing so would leak the decrypted
+    /// payload's effects verbatim.
+    pub fn execute<T: TransactionSet>(
+        &self,
+        key: &SecretKey,
+        mut fork: Fork,
+        gas_budget: Option<u64>,
+    ) -> Result<PrivateTransactionReceipt, PrivateTransactionError> {
+        if !self.verify() {
+            return Err(PrivateTransactionError::NotPermitted);
+        }
+        let raw = RawTransaction::from_vec(decrypt(&self.encrypted_payload, key));
+        let tx_set = T::tx_from_raw(raw).map_err(PrivateTransactionError::Encoding)?;
+        let tx: Box<Transaction> = tx_set.into();
+
+        // The encrypted payload, not the plaintext transaction, is the only thing every
+        // validator — not just the holders of `key` — can hash to identify this execution in
+        // `Schema`, so it's what's used to key the recorded events/gas.
+        let tx_hash = crypto::hash(&self.encrypted_payload);
+        let (result, events, gas_used) = {
+            let mut context = match gas_budget {
+                Some(budget) => ExecutionContext::with_gas_budget(&mut fork, budget),
+                None => ExecutionContext::new(&mut fork),
+            };
+            let exec_result = tx.execute(&mut context);
+            let result = finalize_execution_result(&context, exec_result);
+            let gas_used = context.gas_used();
+            (result, context.into_events(), gas_used)
+        };
+        {
+            let mut schema = Schema::new(&mut fork);
+            schema.set_transaction_events(tx_hash, TransactionEvents::new(events));
+            schema.set_transaction_gas(tx_hash, gas_used);
+        }
+        // Bind the receipt to the actual state change, the same way `simulate_transaction` makes
+        // a transaction's effect inspectable: extract the write set the execution (and the event/
+        // gas bookkeeping above) produced and hash *that*, rather than just the execution status.
+        // The write set itself stays private — only this hash is ever published.
+        let state_diff_hash = extract_write_set(fork).hash();
+        Ok(PrivateTransactionReceipt {
+            // Only the reduced status (no free-text `description`) is exposed publicly, so the
+            // private payload's details can't leak through error text.
+            status: public_status(&result),
+            state_diff_hash,
+        })
+    }
+}
+
+/// Reduces a `TransactionResult` to what's safe to publish for a `PrivateTransaction`: whether it
+/// succeeded, and if not, its `error_type` only — `TransactionError::description` is dropped since
+/// services are free to put arbitrary (and possibly payload-derived) text in it.
+pub type PrivateExecutionStatus = Result<(), TransactionErrorType>;
+
+fn public_status(result: &TransactionResult) -> PrivateExecutionStatus {
+    result.as_ref().map(|_| ()).map_err(TransactionError::error_type)
+}
+
+/// Everything the public chain learns about an executed `PrivateTransaction`: whether it
+/// succeeded, and a hash binding it to the private state change it made — without revealing
+/// either the transaction's contents or the state itself.
+#[derive(Debug, Clone)]
+pub struct PrivateTransactionReceipt {
+    status: PrivateExecutionStatus,
+    state_diff_hash: Hash,
+}
+
+impl PrivateTransactionReceipt {
+    pub fn status(&self) -> PrivateExecutionStatus {
+        self.status.clone()
+    }
+
+    pub fn state_diff_hash(&self) -> Hash {
+        self.state_diff_hash
+    }
+}
+
+/// Errors specific to handling a `PrivateTransaction`.
+#[derive(Debug)]
+pub enum PrivateTransactionError {
+    /// The outer signature didn't match, or `sender` isn't in `permitted_validators`.
+    NotPermitted,
+    /// The decrypted payload didn't decode into a transaction of the expected `TransactionSet`.
+    Encoding(encoding::Error),
+}
+
+impl fmt::Display for PrivateTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PrivateTransactionError::NotPermitted => write!(
+                f,
+                "sender is not a permitted validator for this private transaction"
+            ),
+            PrivateTransactionError::Encoding(ref err) => {
+                write!(f, "failed to decode decrypted transaction: {}", err)
+            }
+        }
+    }
+}
+
+// NOTE: placeholder symmetric cipher good enough to model the private-transaction data flow.
+// This crate has no AEAD dependency to draw on; swap this for an authenticated cipher (e.g.
+// XChaCha20-Poly1305) before `PrivateTransaction` is used with real keys. Encryption and
+// decryption are the same keystream XOR, so one function serves both directions.
+fn decrypt(data: &[u8], key: &SecretKey) -> Vec<u8> {
+    xor_with_keystream(data, key.as_ref())
+}
+
+fn xor_with_keystream(data: &[u8], key: &[u8]) -> Vec<u8> {
+    if key.is_empty() {
+        return data.to_vec();
+    }
+    data.iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ key[i % key.len()])
+        .collect()
+}
+
+/// Authenticates the sender of a transaction. `Transaction::verify` implementations can delegate
+/// to one of these instead of hand-rolling a signature check, the way Diem's authenticators let a
+/// transaction require more than a single Ed25519 signature.
+#[derive(Debug, Clone)]
+pub enum TransactionAuthenticator {
+    /// A `K`-of-`N` multi-signature: `threshold` of `public_keys` must have signed. `bitmap` bit
+    /// `i` (counted from the most significant bit of `bitmap[0]`) set means `public_keys[i]`
+    /// contributed the next signature in `signatures`, in ascending index order.
+    MultiEd25519 {
+        public_keys: Vec<PublicKey>,
+        threshold: u8,
+        bitmap: [u8; 4],
+        signatures: Vec<Signature>,
+    },
+}
+
+impl TransactionAuthenticator {
+    pub fn multi_ed25519(
+        public_keys: Vec<PublicKey>,
+        threshold: u8,
+        bitmap: [u8; 4],
+        signatures: Vec<Signature>,
+    ) -> Self {
+        TransactionAuthenticator::MultiEd25519 {
+            public_keys,
+            threshold,
+            bitmap,
+            signatures,
+        }
+    }
+
+    /// Verifies `message_bytes` against this authenticator.
+    pub fn verify(&self, message_bytes: &[u8]) -> bool {
+        match *self {
+            TransactionAuthenticator::MultiEd25519 {
+                ref public_keys,
+                threshold,
+                bitmap,
+                ref signatures,
+            } => verify_multi_ed25519(public_keys, threshold, bitmap, signatures, message_bytes),
+        }
+    }
+}
+
+fn verify_multi_ed25519(
+    public_keys: &[PublicKey],
+    threshold: u8,
+    bitmap: [u8; 4],
+    signatures: &[Signature],
+    message_bytes: &[u8],
+) -> bool {
+    let n = public_keys.len();
+    if n > 32 {
+        // The bitmap only has 32 bits to index into `public_keys`.
+        return false;
+    }
+    // Trailing bits beyond `n` must be zero: a signer index `>= n` would have nothing to verify
+    // against.
+    if (n..32).any(|bit| bitmap_bit(bitmap, bit)) {
+        return false;
+    }
+
+    let set_indices: Vec<usize> = (0..n).filter(|&i| bitmap_bit(bitmap, i)).collect();
+    if set_indices.len() < threshold as usize || set_indices.len() != signatures.len() {
+        return false;
+    }
+    // Indices are read off the bitmap in ascending order, so this also rejects any duplicate or
+    // out-of-order pairing between `signatures` and the bits that named them.
+    if set_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return false;
+    }
+
+    set_indices
+        .iter()
+        .zip(signatures.iter())
+        .all(|(&index, signature)| crypto::verify(signature, message_bytes, &public_keys[index]))
+}
+
+fn bitmap_bit(bitmap: [u8; 4], index: usize) -> bool {
+    let byte = bitmap[index / 8];
+    let bit = 7 - (index % 8);
+    (byte >> bit) & 1 == 1
+}
+
+/// Convenience for a `Transaction` whose `verify` is exactly "does the authenticator accept the
+/// canonical message bytes". Implementors supply the two accessors and get `verify_multisig` as
+/// their `Transaction::verify` body: `fn verify(&self) -> bool { self.verify_multisig() }`.
+pub trait MultiSigTransaction: Transaction {
+    fn authenticator(&self) -> &TransactionAuthenticator;
+    fn raw_bytes(&self) -> &[u8];
+
+    fn verify_multisig(&self) -> bool {
+        self.authenticator().verify(self.raw_bytes())
+    }
+}
+
+/// A single change recorded in a `WriteSet`: either a value written at a key, or a deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOp {
+    Value(Vec<u8>),
+    Deletion,
+}
+
+impl StorageValue for WriteOp {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            WriteOp::Value(value) => {
+                let mut bytes = vec![1u8];
+                bytes.extend((value.len() as u32).into_bytes());
+                bytes.extend(value);
+                bytes
+            }
+            WriteOp::Deletion => vec![0u8],
+        }
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match bytes[0] {
+            0 => WriteOp::Deletion,
+            1 => {
+                let len = u32::from_bytes(Cow::Borrowed(&bytes[1..5])) as usize;
+                WriteOp::Value(bytes[5..5 + len].to_vec())
+            }
+            tag => panic!("Invalid WriteOp tag: {}", tag),
+        }
+    }
+}
+
+/// An ordered, explicit record of the storage changes a transaction made, extracted from a
+/// throwaway `Fork` instead of merging them into committed state — Diem calls the equivalent
+/// concept a `WriteSet`. Lets a client preview a transaction's effect, and the partial state hash
+/// it would produce, before the transaction is ever broadcast.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteSet {
+    changes: Vec<(Vec<u8>, WriteOp)>,
+}
+
+impl WriteSet {
+    pub fn new() -> Self {
+        WriteSet {
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, key: Vec<u8>, op: WriteOp) {
+        self.changes.push((key, op));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &WriteOp)> {
+        self.changes.iter().map(|&(ref key, ref op)| (key.as_slice(), op))
+    }
+}
+
+impl StorageValue for WriteSet {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = (self.changes.len() as u32).into_bytes();
+        for (key, op) in self.changes {
+            bytes.extend((key.len() as u32).into_bytes());
+            bytes.extend(key);
+            let op_bytes = op.into_bytes();
+            bytes.extend((op_bytes.len() as u32).into_bytes());
+            bytes.extend(op_bytes);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut offset = 0usize;
+        let count = u32::from_bytes(Cow::Borrowed(&bytes[offset..offset + 4])) as usize;
+        offset += 4;
+        let mut changes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let key_len = u32::from_bytes(Cow::Borrowed(&bytes[offset..offset + 4])) as usize;
+            offset += 4;
+            let key = bytes[offset..offset + key_len].to_vec();
+            offset += key_len;
+            let op_len = u32::from_bytes(Cow::Borrowed(&bytes[offset..offset + 4])) as usize;
+            offset += 4;
+            let op = WriteOp::from_bytes(Cow::Borrowed(&bytes[offset..offset + op_len]));
+            offset += op_len;
+            changes.push((key, op));
+        }
+        WriteSet { changes }
+    }
+}
+
+impl CryptoHash for WriteSet {
+    fn hash(&self) -> Hash {
+        let mut bytes = (self.changes.len() as u32).into_bytes();
+        for &(ref key, ref op) in &self.changes {
+            bytes.extend((key.len() as u32).into_bytes());
+            bytes.extend_from_slice(key);
+            bytes.extend(op.clone().into_bytes());
+        }
+        crypto::hash(&bytes)
+    }
+}
+
+/// Runs `transaction.execute` against `fork`, optionally metered by `gas_budget`, and extracts
+/// the changes it made as an explicit `WriteSet` instead of merging them, so a client can preview
+/// the effect — and the resulting partial state hash — before the transaction is ever broadcast.
+/// `fork` is always discarded afterwards regardless of the outcome; this never touches committed
+/// blockchain state. `tx_hash` identifies the transaction being previewed, so the caller can look
+/// its events and charged gas back up afterwards via `Schema::transaction_events`/
+/// `transaction_gas`, exactly as a committed transaction's would be — the write set the events
+/// and gas were recorded into is itself what gets hashed below, so they count towards the
+/// previewed state hash too.
+///
+/// `Blockchain::simulate_transaction` is the public entry point for this: it hands a fork over a
+/// disposable snapshot of the current state to this function and forwards the result.
+pub fn simulate_transaction(
+    transaction: &Transaction,
+    tx_hash: Hash,
+    mut fork: Fork,
+    gas_budget: Option<u64>,
+) -> (TransactionResult, WriteSet) {
+    let (result, events, gas_used) = {
+        let mut context = match gas_budget {
+            Some(budget) => ExecutionContext::with_gas_budget(&mut fork, budget),
+            None => ExecutionContext::new(&mut fork),
+        };
+        let exec_result = transaction.execute(&mut context);
+        let result = finalize_execution_result(&context, exec_result);
+        let gas_used = context.gas_used();
+        (result, context.into_events(), gas_used)
+    };
+    {
+        let mut schema = Schema::new(&mut fork);
+        schema.set_transaction_events(tx_hash, TransactionEvents::new(events));
+        schema.set_transaction_gas(tx_hash, gas_used);
+    }
+    (result, extract_write_set(fork))
+}
+
+// A `Patch` is keyed two levels deep — by index name, then by the key within that index — so two
+// different indexes can legally hold the same raw key without colliding. Recording only the inner
+// key would give such writes identical `WriteSet` keys, so neither the preview nor the state-diff
+// hash it feeds into for private transactions could tell which index a change belongs to.
+// Namespacing every recorded key with its owning index name keeps them distinct.
+fn namespaced_key(index_name: &str, key: &[u8]) -> Vec<u8> {
+    let mut namespaced = (index_name.len() as u32).into_bytes();
+    namespaced.extend(index_name.as_bytes());
+    namespaced.extend(key);
+    namespaced
+}
+
+fn extract_write_set(fork: Fork) -> WriteSet {
+    let mut write_set = WriteSet::new();
+    for (index_name, changes) in fork.into_patch() {
+        for (key, change) in changes {
+            let op = match change {
+                Change::Put(value) => WriteOp::Value(value),
+                Change::Delete => WriteOp::Deletion,
+            };
+            write_set.record(namespaced_key(&index_name, &key), op);
+        }
+    }
+    write_set
+}
+
 /// Tries to get a meaningful description from the given panic.
 fn panic_description(any: &Box<Any + Send>) -> Option<String> {
     if let Some(s) = any.downcast_ref::<&str>() {
@@ -546,4 +1401,295 @@ fn panic_description(any: &Box<Any + Send>) -> Option<String> {
 //    fn create_entry(fork: &mut Fork) -> Entry<&mut Fork, u64> {
 //        Entry::new("transaction_status_test", fork)
 //    }
-//}
\ No newline at end of file
+//}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    #[test]
+    fn transaction_result_structured_roundtrip() {
+        let result: TransactionResult = Err(TransactionError::structured(
+            ErrorCategory::Execution,
+            42,
+            Some((7, 3)),
+            Some("insufficient balance".to_string()),
+        ));
+        let bytes = result.clone().into_bytes();
+        let decoded = TransactionResult::from_bytes(Cow::Borrowed(&bytes));
+        assert_eq!(result, decoded);
+    }
+
+    #[test]
+    fn transaction_result_structured_roundtrip_without_location() {
+        let result: TransactionResult = Err(TransactionError::structured(
+            ErrorCategory::Verification,
+            1,
+            None,
+            None,
+        ));
+        let bytes = result.clone().into_bytes();
+        let decoded = TransactionResult::from_bytes(Cow::Borrowed(&bytes));
+        assert_eq!(result, decoded);
+    }
+
+    #[test]
+    fn transaction_result_structured_hash_ignores_description() {
+        let with_description: TransactionResult = Err(TransactionError::structured(
+            ErrorCategory::Execution,
+            42,
+            Some((7, 3)),
+            Some("some description".to_string()),
+        ));
+        let without_description: TransactionResult = Err(TransactionError::structured(
+            ErrorCategory::Execution,
+            42,
+            Some((7, 3)),
+            None,
+        ));
+        assert_eq!(with_description.hash(), without_description.hash());
+    }
+}
+
+#[cfg(test)]
+mod private_transaction_tests {
+    use super::*;
+
+    #[test]
+    fn verify_accepts_permitted_signer() {
+        let (sender, sender_key) = crypto::gen_keypair();
+        let (other, _) = crypto::gen_keypair();
+        let payload = b"encrypted-payload".to_vec();
+        let signature = crypto::sign(&payload, &sender_key);
+        let private_tx = PrivateTransaction::new(payload, vec![other, sender], sender, signature);
+        assert!(private_tx.verify());
+    }
+
+    #[test]
+    fn verify_rejects_signer_outside_permitted_set() {
+        let (sender, sender_key) = crypto::gen_keypair();
+        let (other, _) = crypto::gen_keypair();
+        let payload = b"encrypted-payload".to_vec();
+        let signature = crypto::sign(&payload, &sender_key);
+        // `sender` itself isn't in `permitted_validators`, so the envelope is rejected even
+        // though the signature itself is valid.
+        let private_tx = PrivateTransaction::new(payload, vec![other], sender, signature);
+        assert!(!private_tx.verify());
+    }
+
+    #[test]
+    fn verify_rejects_forged_signature() {
+        let (sender, _) = crypto::gen_keypair();
+        let (_, wrong_key) = crypto::gen_keypair();
+        let payload = b"encrypted-payload".to_vec();
+        let signature = crypto::sign(&payload, &wrong_key);
+        let private_tx = PrivateTransaction::new(payload, vec![sender], sender, signature);
+        assert!(!private_tx.verify());
+    }
+
+    #[test]
+    fn public_status_drops_description_but_keeps_error_type() {
+        let result: TransactionResult = Err(TransactionError::code(
+            5,
+            Some("leaks payload-derived details".to_string()),
+        ));
+        let status = public_status(&result);
+        assert_eq!(status, Err(TransactionErrorType::Code(5)));
+    }
+
+    #[test]
+    fn public_status_is_ok_on_success() {
+        let result: TransactionResult = Ok(());
+        assert_eq!(public_status(&result), Ok(()));
+    }
+}
+
+#[cfg(test)]
+mod multisig_tests {
+    use super::*;
+
+    fn keys(n: usize) -> Vec<(PublicKey, SecretKey)> {
+        (0..n).map(|_| crypto::gen_keypair()).collect()
+    }
+
+    fn bitmap_for(indices: &[usize]) -> [u8; 4] {
+        let mut bitmap = [0u8; 4];
+        for &index in indices {
+            bitmap[index / 8] |= 1 << (7 - (index % 8));
+        }
+        bitmap
+    }
+
+    #[test]
+    fn accepts_exactly_threshold_signatures_in_order() {
+        let signers = keys(4);
+        let public_keys: Vec<_> = signers.iter().map(|(pk, _)| *pk).collect();
+        let message = b"multisig message";
+        let signatures: Vec<_> = [0usize, 2]
+            .iter()
+            .map(|&i| crypto::sign(message, &signers[i].1))
+            .collect();
+        let bitmap = bitmap_for(&[0, 2]);
+        assert!(verify_multi_ed25519(
+            &public_keys,
+            2,
+            bitmap,
+            &signatures,
+            message
+        ));
+    }
+
+    #[test]
+    fn rejects_when_signer_count_is_below_threshold() {
+        let signers = keys(4);
+        let public_keys: Vec<_> = signers.iter().map(|(pk, _)| *pk).collect();
+        let message = b"multisig message";
+        let signatures = vec![crypto::sign(message, &signers[0].1)];
+        let bitmap = bitmap_for(&[0]);
+        assert!(!verify_multi_ed25519(
+            &public_keys,
+            2,
+            bitmap,
+            &signatures,
+            message
+        ));
+    }
+
+    #[test]
+    fn rejects_when_signature_count_does_not_match_bitmap() {
+        let signers = keys(4);
+        let public_keys: Vec<_> = signers.iter().map(|(pk, _)| *pk).collect();
+        let message = b"multisig message";
+        // Bitmap names two signers but only one signature is supplied.
+        let signatures = vec![crypto::sign(message, &signers[0].1)];
+        let bitmap = bitmap_for(&[0, 2]);
+        assert!(!verify_multi_ed25519(
+            &public_keys,
+            1,
+            bitmap,
+            &signatures,
+            message
+        ));
+    }
+
+    #[test]
+    fn rejects_trailing_bitmap_bits_beyond_key_count() {
+        let signers = keys(4);
+        let public_keys: Vec<_> = signers.iter().map(|(pk, _)| *pk).collect();
+        let message = b"multisig message";
+        let signatures = vec![crypto::sign(message, &signers[0].1)];
+        // Bit 10 is set but there are only 4 keys (indices 0..=3): the index is out of range.
+        let bitmap = bitmap_for(&[0, 10]);
+        assert!(!verify_multi_ed25519(
+            &public_keys,
+            1,
+            bitmap,
+            &signatures,
+            message
+        ));
+    }
+
+    #[test]
+    fn rejects_forged_signature_for_a_named_signer() {
+        let signers = keys(4);
+        let public_keys: Vec<_> = signers.iter().map(|(pk, _)| *pk).collect();
+        let message = b"multisig message";
+        // Signed by signer 1's key but claimed via the bitmap to be signer 0's contribution.
+        let signatures = vec![crypto::sign(message, &signers[1].1)];
+        let bitmap = bitmap_for(&[0]);
+        assert!(!verify_multi_ed25519(
+            &public_keys,
+            1,
+            bitmap,
+            &signatures,
+            message
+        ));
+    }
+}
+
+#[cfg(test)]
+mod write_set_tests {
+    use super::*;
+    use storage::{Database, MemoryDB};
+
+    #[test]
+    fn extract_write_set_keeps_same_key_in_different_indexes_distinct() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        {
+            let mut first: MapIndex<&mut Fork, Vec<u8>, Vec<u8>> =
+                MapIndex::new("first_index", &mut fork);
+            first.put(&b"shared-key".to_vec(), b"value-in-first".to_vec());
+        }
+        {
+            let mut second: MapIndex<&mut Fork, Vec<u8>, Vec<u8>> =
+                MapIndex::new("second_index", &mut fork);
+            second.put(&b"shared-key".to_vec(), b"value-in-second".to_vec());
+        }
+
+        let write_set = extract_write_set(fork);
+
+        // Both writes must survive as distinct entries under distinct keys: if the index name
+        // weren't namespaced into the recorded key, both would be recorded as `shared-key`.
+        assert_eq!(write_set.iter().count(), 2);
+        let keys: Vec<&[u8]> = write_set.iter().map(|(key, _)| key).collect();
+        assert_ne!(keys[0], keys[1]);
+    }
+}
+
+#[cfg(test)]
+mod gas_tests {
+    use super::*;
+    use storage::{Database, MemoryDB};
+
+    #[test]
+    fn charge_succeeds_within_budget() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        let mut context = ExecutionContext::with_gas_budget(&mut fork, 10);
+        assert_eq!(context.charge(4), Ok(()));
+        assert_eq!(context.charge(6), Ok(()));
+        assert_eq!(context.gas_used(), 10);
+    }
+
+    #[test]
+    fn charge_fails_once_budget_is_exceeded() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        let mut context = ExecutionContext::with_gas_budget(&mut fork, 10);
+        assert_eq!(context.charge(4), Ok(()));
+        assert_eq!(context.charge(7), Err(()));
+    }
+
+    #[test]
+    fn finalize_forces_out_of_resources_even_if_execute_returned_ok() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        let mut context = ExecutionContext::with_gas_budget(&mut fork, 10);
+        // A misbehaving transaction ignores a failed `charge` and returns `Ok` anyway.
+        let _ = context.charge(11);
+        let result = finalize_execution_result(&context, Ok(()));
+        assert_eq!(
+            result.unwrap_err().error_type(),
+            TransactionErrorType::OutOfResources
+        );
+    }
+
+    #[test]
+    fn unmetered_context_never_refuses_a_charge() {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        let mut context = ExecutionContext::new(&mut fork);
+        assert_eq!(context.charge(u64::max_value()), Ok(()));
+    }
+
+    #[test]
+    fn transaction_result_out_of_resources_roundtrip() {
+        let result: TransactionResult = Err(TransactionError::out_of_resources(Some(
+            "charged 11 against a budget of 10".to_string(),
+        )));
+        let bytes = result.clone().into_bytes();
+        let decoded = TransactionResult::from_bytes(Cow::Borrowed(&bytes));
+        assert_eq!(result, decoded);
+    }
+}
\ No newline at end of file